@@ -36,7 +36,9 @@ fn lookup_def<'h>(l: &Id, defs: &'h HashMap<Id, usize>) -> &'h usize {
     }
 }
 
-fn get_regular_labels<'arena>(instr: &'arena Instruct<'arena>) -> Vec<&'arena Label<'arena>> {
+// `ILabel` is a label *definition*, not a reference, so it is deliberately
+// left out here; `for_each_label_mut` below additionally visits it.
+fn for_each_label<'a, 'arena>(instr: &'a Instruct<'arena>, mut f: impl FnMut(&'a Label<'arena>)) {
     use Instruct::*;
     use InstructCall::*;
     use InstructControlFlow::*;
@@ -58,14 +60,129 @@ fn get_regular_labels<'arena>(instr: &'arena Instruct<'arena>) -> Vec<&'arena La
         | ICall(FCallFunc(FcallArgs(_, _, _, _, Some(l), _)))
         | ICall(FCallFuncD(FcallArgs(_, _, _, _, Some(l), _), _))
         | ICall(FCallObjMethod(FcallArgs(_, _, _, _, Some(l), _), _))
-        | ICall(FCallObjMethodD(FcallArgs(_, _, _, _, Some(l), _), _, _)) => vec![l],
-        IContFlow(Switch(_, _, ls)) => ls.iter().collect::<Vec<_>>(),
-        IContFlow(SSwitch(pairs)) => pairs.iter().map(|x| &x.1).collect::<Vec<_>>(),
-        IMisc(MemoGetEager(l1, l2, _)) => vec![l1, l2],
-        _ => vec![],
+        | ICall(FCallObjMethodD(FcallArgs(_, _, _, _, Some(l), _), _, _)) => f(l),
+        IContFlow(Switch(_, _, ls)) => ls.iter().for_each(f),
+        IContFlow(SSwitch(pairs)) => pairs.iter().for_each(|x| f(&x.1)),
+        IMisc(MemoGetEager(l1, l2, _)) => {
+            f(l1);
+            f(l2);
+        }
+        _ => {}
     }
 }
 
+fn for_each_label_mut<'arena>(instr: &mut Instruct<'arena>, mut f: impl FnMut(&mut Label<'arena>)) {
+    use Instruct::*;
+    use InstructCall::*;
+    use InstructControlFlow::*;
+    use InstructIterator::*;
+    use InstructMisc::*;
+    match instr {
+        IIterator(IterInit(_, l))
+        | IIterator(IterNext(_, l))
+        | ICall(FCall(FcallArgs(_, _, _, _, Some(l), _)))
+        | ICall(FCallClsMethod(FcallArgs(_, _, _, _, Some(l), _), _))
+        | ICall(FCallClsMethodD(FcallArgs(_, _, _, _, Some(l), _), _, _))
+        | ICall(FCallClsMethodS(FcallArgs(_, _, _, _, Some(l), _), _))
+        | ICall(FCallClsMethodSD(FcallArgs(_, _, _, _, Some(l), _), _, _))
+        | ICall(FCallFunc(FcallArgs(_, _, _, _, Some(l), _)))
+        | ICall(FCallFuncD(FcallArgs(_, _, _, _, Some(l), _), _))
+        | ICall(FCallObjMethod(FcallArgs(_, _, _, _, Some(l), _), _))
+        | ICall(FCallObjMethodD(FcallArgs(_, _, _, _, Some(l), _), _, _))
+        | IContFlow(Jmp(l))
+        | IContFlow(JmpNS(l))
+        | IContFlow(JmpZ(l))
+        | IContFlow(JmpNZ(l))
+        | IMisc(MemoGet(l, _))
+        | ILabel(l) => f(l),
+        IContFlow(Switch(_, _, ll)) => ll.iter_mut().for_each(f),
+        IContFlow(SSwitch(pairs)) => pairs.iter_mut().for_each(|(_, l)| f(l)),
+        IMisc(MemoGetEager(l1, l2, _)) => {
+            f(l1);
+            f(l2);
+        }
+        _ => {}
+    }
+}
+
+/// Errors produced by [`verify_labels`] when a function body's label
+/// definitions or references are malformed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LabelError {
+    /// The same label id is defined (via `ILabel`) more than once.
+    Duplicate(Id),
+    /// A label is referenced but never defined anywhere in the body.
+    Undefined(Id),
+    /// A label still carries a name instead of having been rewritten to
+    /// its final numeric id.
+    Unrewritten,
+}
+
+impl std::fmt::Display for LabelError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LabelError::Duplicate(id) => write!(f, "label {:?} defined more than once", id),
+            LabelError::Undefined(id) => write!(f, "label {:?} referenced but never defined", id),
+            LabelError::Unrewritten => {
+                write!(f, "label was never rewritten to its final numeric id")
+            }
+        }
+    }
+}
+
+impl std::error::Error for LabelError {}
+
+// Non-panicking counterpart of `create_label_to_offset_map` /
+// `create_label_ref_map`: collects every label definition (erroring on a
+// duplicate), then checks every reference visited by `for_each_label`
+// resolves to one of those definitions.
+pub fn verify_labels<'arena>(
+    params: &[HhasParam<'arena>],
+    body: &InstrSeq<'arena>,
+) -> Result<(), LabelError> {
+    let mut folder = |(i, defs): (usize, Result<HashMap<Id, usize>, LabelError>),
+                       instr: &Instruct<'arena>| match instr {
+        Instruct::ILabel(l) => {
+            let defs = defs.and_then(|mut defs| {
+                let id = *Label::id(l).map_err(|_| LabelError::Unrewritten)?;
+                if defs.insert(id, i).is_some() {
+                    Err(LabelError::Duplicate(id))
+                } else {
+                    Ok(defs)
+                }
+            });
+            (i, defs)
+        }
+        _ => (i + 1, defs),
+    };
+    let defs = body.fold_left(&mut folder, (0, Ok(HashMap::new()))).1?;
+
+    let check_ref = |result: Result<(), LabelError>, l: &Label<'arena>| {
+        result.and_then(|()| {
+            let id = *Label::id(l).map_err(|_| LabelError::Unrewritten)?;
+            if defs.contains_key(&id) {
+                Ok(())
+            } else {
+                Err(LabelError::Undefined(id))
+            }
+        })
+    };
+    let result = body.fold_left(
+        &mut |result: Result<(), LabelError>, instr: &Instruct<'arena>| {
+            let mut result = result;
+            for_each_label(instr, |l| result = check_ref(result, l));
+            result
+        },
+        Ok(()),
+    );
+    params
+        .iter()
+        .fold(result, |result, param| match &param.default_value {
+            None => result,
+            Some((l, _)) => check_ref(result, l),
+        })
+}
+
 fn create_label_ref_map<'arena>(
     defs: &HashMap<Id, usize>,
     params: &[HhasParam<'arena>],
@@ -88,10 +205,9 @@ fn create_label_ref_map<'arena>(
     let gather_using =
         |acc: (usize, (HashSet<Id>, HashMap<Id, usize>)), instrseq: &InstrSeq<'arena>| {
             let mut folder =
-                |acc: (usize, (HashSet<Id>, HashMap<Id, usize>)), instr: &Instruct<'arena>| {
-                    (get_regular_labels(instr))
-                        .into_iter()
-                        .fold(acc, process_ref)
+                |mut acc: (usize, (HashSet<Id>, HashMap<Id, usize>)), instr: &Instruct<'arena>| {
+                    for_each_label(instr, |l| acc = process_ref(std::mem::take(&mut acc), l));
+                    acc
                 };
             instrseq.fold_left(&mut folder, acc)
         };
@@ -112,37 +228,7 @@ fn relabel_instr<'arena, F>(instr: &mut Instruct<'arena>, relabel: &mut F)
 where
     F: FnMut(&mut Label<'arena>),
 {
-    use Instruct::*;
-    use InstructCall::*;
-    use InstructControlFlow::*;
-    use InstructIterator::*;
-    use InstructMisc::*;
-    match instr {
-        IIterator(IterInit(_, l))
-        | IIterator(IterNext(_, l))
-        | ICall(FCall(FcallArgs(_, _, _, _, Some(l), _)))
-        | ICall(FCallClsMethod(FcallArgs(_, _, _, _, Some(l), _), _))
-        | ICall(FCallClsMethodD(FcallArgs(_, _, _, _, Some(l), _), _, _))
-        | ICall(FCallClsMethodS(FcallArgs(_, _, _, _, Some(l), _), _))
-        | ICall(FCallClsMethodSD(FcallArgs(_, _, _, _, Some(l), _), _, _))
-        | ICall(FCallFunc(FcallArgs(_, _, _, _, Some(l), _)))
-        | ICall(FCallFuncD(FcallArgs(_, _, _, _, Some(l), _), _))
-        | ICall(FCallObjMethod(FcallArgs(_, _, _, _, Some(l), _), _))
-        | ICall(FCallObjMethodD(FcallArgs(_, _, _, _, Some(l), _), _, _))
-        | IContFlow(Jmp(l))
-        | IContFlow(JmpNS(l))
-        | IContFlow(JmpZ(l))
-        | IContFlow(JmpNZ(l))
-        | IMisc(MemoGet(l, _))
-        | ILabel(l) => relabel(l),
-        IContFlow(Switch(_, _, ll)) => ll.iter_mut().for_each(|l| relabel(l)),
-        IContFlow(SSwitch(pairs)) => pairs.iter_mut().for_each(|(_, l)| relabel(l)),
-        IMisc(MemoGetEager(l1, l2, _)) => {
-            relabel(l1);
-            relabel(l2);
-        }
-        _ => {}
-    }
+    for_each_label_mut(instr, relabel)
 }
 
 fn rewrite_params_and_body<'arena>(
@@ -188,6 +274,297 @@ fn rewrite_params_and_body<'arena>(
     body.filter_map_mut(alloc, &mut rewrite_instr);
 }
 
+// True for instructions that end a basic block, i.e. the instruction right
+// after one of these always starts a fresh block even when that instruction
+// turns out to be unreachable. This includes conditional branches whose
+// label is only taken sometimes -- `IterInit`/`IterNext` (jump when the
+// iterator is empty/exhausted) and an `FCall*` with an async-eager-execution
+// label (jump on eager completion) both still fall through on the other
+// branch, so they terminate a block the same way `JmpZ`/`JmpNZ` do.
+fn is_terminator<'arena>(instr: &Instruct<'arena>) -> bool {
+    use Instruct::*;
+    use InstructCall::*;
+    use InstructControlFlow::*;
+    use InstructIterator::*;
+    use InstructMisc::*;
+    matches!(
+        instr,
+        IContFlow(Jmp(_))
+            | IContFlow(JmpNS(_))
+            | IContFlow(JmpZ(_))
+            | IContFlow(JmpNZ(_))
+            | IContFlow(Switch(_, _, _))
+            | IContFlow(SSwitch(_))
+            | IContFlow(RetC)
+            | IContFlow(RetM(..))
+            | IContFlow(Throw)
+            | IIterator(IterInit(_, _))
+            | IIterator(IterNext(_, _))
+            | IMisc(MemoGet(_, _))
+            | IMisc(MemoGetEager(_, _, _))
+            | IMisc(Fatal(_))
+            | ICall(FCall(FcallArgs(_, _, _, _, Some(_), _)))
+            | ICall(FCallClsMethod(FcallArgs(_, _, _, _, Some(_), _), _))
+            | ICall(FCallClsMethodD(FcallArgs(_, _, _, _, Some(_), _), _, _))
+            | ICall(FCallClsMethodS(FcallArgs(_, _, _, _, Some(_), _), _))
+            | ICall(FCallClsMethodSD(FcallArgs(_, _, _, _, Some(_), _), _, _))
+            | ICall(FCallFunc(FcallArgs(_, _, _, _, Some(_), _)))
+            | ICall(FCallFuncD(FcallArgs(_, _, _, _, Some(_), _), _))
+            | ICall(FCallObjMethod(FcallArgs(_, _, _, _, Some(_), _), _))
+            | ICall(FCallObjMethodD(FcallArgs(_, _, _, _, Some(_), _), _, _))
+    )
+}
+
+// The instruction indices a terminator can transfer control to, in the same
+// index space as `create_label_to_offset_map`. `fallthrough` is the index
+// right after the terminator, if any code follows it in layout order.
+fn terminator_successors<'arena>(
+    instr: &Instruct<'arena>,
+    defs: &HashMap<Id, usize>,
+    fallthrough: Option<usize>,
+) -> Vec<usize> {
+    use Instruct::*;
+    use InstructCall::*;
+    use InstructControlFlow::*;
+    use InstructIterator::*;
+    use InstructMisc::*;
+    let target = |l: &Label<'arena>| {
+        *lookup_def(
+            Label::id(l).expect("Label should've been rewritten by this point"),
+            defs,
+        )
+    };
+    match instr {
+        IContFlow(Jmp(l)) | IContFlow(JmpNS(l)) => vec![target(l)],
+        IContFlow(JmpZ(l)) | IContFlow(JmpNZ(l)) => {
+            let mut targets = vec![target(l)];
+            targets.extend(fallthrough);
+            targets
+        }
+        IIterator(IterInit(_, l)) | IIterator(IterNext(_, l)) => {
+            let mut targets = vec![target(l)];
+            targets.extend(fallthrough);
+            targets
+        }
+        IMisc(MemoGet(l, _)) => {
+            let mut targets = vec![target(l)];
+            targets.extend(fallthrough);
+            targets
+        }
+        IMisc(MemoGetEager(l1, l2, _)) => {
+            let mut targets = vec![target(l1), target(l2)];
+            targets.extend(fallthrough);
+            targets
+        }
+        ICall(FCall(FcallArgs(_, _, _, _, Some(l), _)))
+        | ICall(FCallClsMethod(FcallArgs(_, _, _, _, Some(l), _), _))
+        | ICall(FCallClsMethodD(FcallArgs(_, _, _, _, Some(l), _), _, _))
+        | ICall(FCallClsMethodS(FcallArgs(_, _, _, _, Some(l), _), _))
+        | ICall(FCallClsMethodSD(FcallArgs(_, _, _, _, Some(l), _), _, _))
+        | ICall(FCallFunc(FcallArgs(_, _, _, _, Some(l), _)))
+        | ICall(FCallFuncD(FcallArgs(_, _, _, _, Some(l), _), _))
+        | ICall(FCallObjMethod(FcallArgs(_, _, _, _, Some(l), _), _))
+        | ICall(FCallObjMethodD(FcallArgs(_, _, _, _, Some(l), _), _, _)) => {
+            let mut targets = vec![target(l)];
+            targets.extend(fallthrough);
+            targets
+        }
+        IContFlow(Switch(_, _, ls)) => ls.iter().map(target).collect(),
+        IContFlow(SSwitch(pairs)) => pairs.iter().map(|(_, l)| target(l)).collect(),
+        IContFlow(RetC) | IContFlow(RetM(..)) | IContFlow(Throw) | IMisc(Fatal(_)) => vec![],
+        _ => fallthrough.into_iter().collect(),
+    }
+}
+
+// Reachability-based dead-code elimination: removes instructions that can
+// never execute. Builds basic blocks over `body`, marks the ones reachable
+// from the function entry and every parameter's DV-initializer, and filters
+// out the rest. Run before `relabel_function` so the label ids it frees up
+// get compacted away.
+pub fn dce_function<'arena>(
+    alloc: &'arena bumpalo::Bump,
+    params: &[HhasParam<'arena>],
+    body: &mut InstrSeq<'arena>,
+) {
+    let defs = create_label_to_offset_map(body);
+
+    // Non-label instructions in program order, indexed exactly like
+    // `create_label_to_offset_map`: an `ILabel` shares the index of the
+    // instruction that follows it rather than consuming one of its own.
+    let instrs: Vec<(usize, Instruct<'arena>)> = body
+        .fold_left(
+            &mut |(i, mut acc): (usize, Vec<(usize, Instruct<'arena>)>),
+                  instr: &Instruct<'arena>| match instr {
+                Instruct::ILabel(_) => (i, acc),
+                other => {
+                    acc.push((i, other.clone()));
+                    (i + 1, acc)
+                }
+            },
+            (0, Vec::new()),
+        )
+        .1;
+    if instrs.is_empty() {
+        return;
+    }
+
+    // Block boundaries: index 0, every label target, and the index right
+    // after every terminator.
+    let mut boundaries: HashSet<usize> = defs.values().copied().collect();
+    boundaries.insert(0);
+    for (i, instr) in &instrs {
+        if is_terminator(instr) {
+            boundaries.insert(i + 1);
+        }
+    }
+    let mut boundaries: Vec<usize> = boundaries.into_iter().filter(|b| *b < instrs.len()).collect();
+    boundaries.sort_unstable();
+
+    // Map each instruction index to its block, and each block to its
+    // [start, end) range.
+    let mut block_of: HashMap<usize, usize> = HashMap::new();
+    let mut block_ranges: Vec<(usize, usize)> = Vec::new();
+    for (block_id, &start) in boundaries.iter().enumerate() {
+        let end = boundaries.get(block_id + 1).copied().unwrap_or(instrs.len());
+        block_ranges.push((start, end));
+        for i in start..end {
+            block_of.insert(i, block_id);
+        }
+    }
+
+    let successors: Vec<Vec<usize>> = block_ranges
+        .iter()
+        .map(|&(_, end)| {
+            let (_, last_instr) = &instrs[end - 1];
+            let fallthrough = if end < instrs.len() { Some(end) } else { None };
+            terminator_successors(last_instr, &defs, fallthrough)
+                .into_iter()
+                .filter_map(|ix| block_of.get(&ix).copied())
+                .collect()
+        })
+        .collect();
+
+    // Seed the worklist with the entry block and every DV-initializer (a
+    // parameter default value is a real entry point, not just a reference).
+    let mut worklist: Vec<usize> = vec![0];
+    for param in params {
+        if let Some((l, _)) = &param.default_value {
+            let id = Label::id(l).expect("Label should've been rewritten by this point");
+            worklist.push(block_of[lookup_def(id, &defs)]);
+        }
+    }
+    let mut reachable: HashSet<usize> = HashSet::new();
+    while let Some(block_id) = worklist.pop() {
+        if reachable.insert(block_id) {
+            worklist.extend(successors[block_id].iter().copied());
+        }
+    }
+
+    let mut i = 0;
+    body.filter_map_mut(alloc, &mut |instr: &mut Instruct<'arena>| match instr {
+        Instruct::ILabel(_) => true,
+        _ => {
+            let keep = reachable.contains(&block_of[&i]);
+            i += 1;
+            keep
+        }
+    });
+}
+
+// Follows a chain of unconditional jumps: `Jmp L1; ...; L1: Jmp L2; ...;
+// L2: Jmp L3; ...; L3: <real code>` resolves to `L3`. Stops as soon as a
+// label is about to be revisited, so a cycle of jumps terminates instead of
+// looping forever.
+fn resolve_jump_chain<'arena>(
+    id: Id,
+    defs: &HashMap<Id, usize>,
+    instrs: &[Instruct<'arena>],
+) -> Id {
+    use Instruct::*;
+    use InstructControlFlow::*;
+    let mut current = id;
+    let mut visited = HashSet::new();
+    visited.insert(current);
+    loop {
+        let ix = *lookup_def(&current, defs);
+        let next = match instrs.get(ix) {
+            Some(IContFlow(Jmp(l))) | Some(IContFlow(JmpNS(l))) => {
+                *Label::id(l).expect("Label should've been rewritten by this point")
+            }
+            _ => return current,
+        };
+        if !visited.insert(next) {
+            return current;
+        }
+        current = next;
+    }
+}
+
+// True when `l` (a `Jmp`/`JmpNS` found at real-instruction index `ix`)
+// targets the label immediately following it in layout order, i.e. it's a
+// jump straight to its own fallthrough and can be deleted outright.
+fn is_jump_to_fallthrough(l: &Label, ix: usize, defs: &HashMap<Id, usize>) -> bool {
+    let id = Label::id(l).expect("Label should've been rewritten by this point");
+    *lookup_def(id, defs) == ix + 1
+}
+
+// Jump-threading / trivial-jump elimination: shortens control flow using
+// the same label/offset machinery as `relabel_function`. Some labels are
+// left unreferenced by this, so run `relabel_function` afterwards to
+// compact them away.
+pub fn thread_jumps<'arena>(alloc: &'arena bumpalo::Bump, body: &mut InstrSeq<'arena>) {
+    use Instruct::*;
+    use InstructControlFlow::*;
+
+    let defs = create_label_to_offset_map(body);
+
+    // Non-label instructions in program order, indexed exactly like
+    // `create_label_to_offset_map`.
+    let instrs: Vec<Instruct<'arena>> = body
+        .fold_left(
+            &mut |(i, mut acc): (usize, Vec<Instruct<'arena>>), instr: &Instruct<'arena>| {
+                match instr {
+                    Instruct::ILabel(_) => (i, acc),
+                    other => {
+                        acc.push(other.clone());
+                        (i + 1, acc)
+                    }
+                }
+            },
+            (0, Vec::new()),
+        )
+        .1;
+    if instrs.is_empty() {
+        return;
+    }
+
+    body.map_mut(&mut |instr: &mut Instruct<'arena>| {
+        if let IContFlow(Jmp(l)) | IContFlow(JmpNS(l)) | IContFlow(JmpZ(l)) | IContFlow(JmpNZ(l)) =
+            instr
+        {
+            let id = *Label::id(l).expect("Label should've been rewritten by this point");
+            let threaded = resolve_jump_chain(id, &defs, &instrs);
+            if threaded != id {
+                l.map_mut(|cur| *cur = threaded);
+            }
+        }
+    });
+
+    let mut i = 0;
+    body.filter_map_mut(alloc, &mut |instr: &mut Instruct<'arena>| match instr {
+        Instruct::ILabel(_) => true,
+        IContFlow(Jmp(l)) | IContFlow(JmpNS(l)) => {
+            let is_fallthrough_jump = is_jump_to_fallthrough(l, i, &defs);
+            i += 1;
+            !is_fallthrough_jump
+        }
+        _ => {
+            i += 1;
+            true
+        }
+    });
+}
+
 pub fn relabel_function<'arena>(
     alloc: &'arena bumpalo::Bump,
     params: &mut Vec<HhasParam<'arena>>,
@@ -198,6 +575,18 @@ pub fn relabel_function<'arena>(
     rewrite_params_and_body(alloc, &defs, &used, &refs, params, body)
 }
 
+// Fallible counterpart of `relabel_function` for callers that can't
+// guarantee `body` is already well-formed.
+pub fn try_relabel_function<'arena>(
+    alloc: &'arena bumpalo::Bump,
+    params: &mut Vec<HhasParam<'arena>>,
+    body: &mut InstrSeq<'arena>,
+) -> Result<(), LabelError> {
+    verify_labels(params, body)?;
+    relabel_function(alloc, params, body);
+    Ok(())
+}
+
 pub fn clone_with_fresh_regular_labels<'arena>(
     alloc: &'arena bumpalo::Bump,
     emitter: &mut Emitter<'arena>,
@@ -238,3 +627,155 @@ pub fn clone_with_fresh_regular_labels<'arena>(
         block.map_mut(&mut |instr| relabel_instr(instr, &mut |l| relabel(l)))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fcall_args_with_eager(eager: Option<Label<'static>>) -> FcallArgs<'static> {
+        FcallArgs(
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            eager,
+            Default::default(),
+        )
+    }
+
+    // Regression test for a bug where `is_terminator`/`terminator_successors`
+    // treated an `FCall*` with an async-eager-execution label as an ordinary
+    // fallthrough instruction: the eager label's block was then never marked
+    // a successor of anything, so `dce_function` deleted it out from under a
+    // live `FCallFuncD ... Some(L_eager)`.
+    #[test]
+    fn fcall_async_eager_label_is_a_terminator_with_both_successors() {
+        let defs: HashMap<Id, usize> = [(7, 3)].iter().copied().collect();
+        let instr = Instruct::ICall(InstructCall::FCallFuncD(
+            fcall_args_with_eager(Some(Label::Regular(7))),
+            Default::default(),
+        ));
+        assert!(is_terminator(&instr));
+        let mut successors = terminator_successors(&instr, &defs, Some(1));
+        successors.sort_unstable();
+        assert_eq!(successors, vec![1, 3]);
+    }
+
+    #[test]
+    fn fcall_without_eager_label_is_not_a_terminator() {
+        let instr = Instruct::ICall(InstructCall::FCallFuncD(
+            fcall_args_with_eager(None),
+            Default::default(),
+        ));
+        assert!(!is_terminator(&instr));
+    }
+
+    #[test]
+    fn iter_init_is_a_terminator_with_both_successors() {
+        let defs: HashMap<Id, usize> = [(5, 10)].iter().copied().collect();
+        let instr = Instruct::IIterator(InstructIterator::IterInit(
+            Default::default(),
+            Label::Regular(5),
+        ));
+        assert!(is_terminator(&instr));
+        let mut successors = terminator_successors(&instr, &defs, Some(2));
+        successors.sort_unstable();
+        assert_eq!(successors, vec![2, 10]);
+    }
+
+    #[test]
+    fn resolve_jump_chain_terminates_on_a_cycle() {
+        // index 0: `L1: Jmp L2`, index 1: `L2: Jmp L1`.
+        let defs: HashMap<Id, usize> = [(1, 0), (2, 1)].iter().copied().collect();
+        let instrs = vec![
+            Instruct::IContFlow(InstructControlFlow::Jmp(Label::Regular(2))),
+            Instruct::IContFlow(InstructControlFlow::Jmp(Label::Regular(1))),
+        ];
+        // Must return without looping forever; which id comes back depends on
+        // where the cycle is entered, but it must be one of the two labels.
+        let resolved = resolve_jump_chain(1, &defs, &instrs);
+        assert!(resolved == 1 || resolved == 2);
+    }
+
+    #[test]
+    fn resolve_jump_chain_follows_a_chain_to_real_code() {
+        // index 0: `L1: Jmp L2`, index 1: `L2: Jmp L3`, index 2: `L3: RetC`.
+        let defs: HashMap<Id, usize> = [(1, 0), (2, 1), (3, 2)].iter().copied().collect();
+        let instrs = vec![
+            Instruct::IContFlow(InstructControlFlow::Jmp(Label::Regular(2))),
+            Instruct::IContFlow(InstructControlFlow::Jmp(Label::Regular(3))),
+            Instruct::IContFlow(InstructControlFlow::RetC),
+        ];
+        assert_eq!(resolve_jump_chain(1, &defs, &instrs), 3);
+    }
+
+    #[test]
+    fn is_jump_to_fallthrough_detects_a_jump_to_the_next_instruction() {
+        let defs: HashMap<Id, usize> = [(9, 4)].iter().copied().collect();
+        let label = Label::Regular(9);
+        assert!(is_jump_to_fallthrough(&label, 3, &defs));
+        assert!(!is_jump_to_fallthrough(&label, 0, &defs));
+    }
+
+    #[test]
+    fn verify_labels_rejects_a_duplicate_definition() {
+        let body = InstrSeq::List(vec![
+            Instruct::ILabel(Label::Regular(0)),
+            Instruct::IContFlow(InstructControlFlow::RetC),
+            Instruct::ILabel(Label::Regular(0)),
+        ]);
+        assert_eq!(verify_labels(&[], &body), Err(LabelError::Duplicate(0)));
+    }
+
+    #[test]
+    fn verify_labels_rejects_a_reference_with_no_definition() {
+        let body = InstrSeq::List(vec![Instruct::IContFlow(InstructControlFlow::Jmp(
+            Label::Regular(42),
+        ))]);
+        assert_eq!(verify_labels(&[], &body), Err(LabelError::Undefined(42)));
+    }
+
+    #[test]
+    fn verify_labels_rejects_an_unrewritten_named_label() {
+        let body = InstrSeq::List(vec![Instruct::ILabel(Label::Named("foo".to_string()))]);
+        assert_eq!(verify_labels(&[], &body), Err(LabelError::Unrewritten));
+    }
+
+    #[test]
+    fn verify_labels_accepts_a_well_formed_body() {
+        let body = InstrSeq::List(vec![
+            Instruct::IContFlow(InstructControlFlow::Jmp(Label::Regular(5))),
+            Instruct::ILabel(Label::Regular(5)),
+            Instruct::IContFlow(InstructControlFlow::RetC),
+        ]);
+        assert_eq!(verify_labels(&[], &body), Ok(()));
+    }
+
+    #[test]
+    fn try_relabel_function_succeeds_and_compacts_a_well_formed_body() {
+        let alloc = bumpalo::Bump::new();
+        let mut params: Vec<HhasParam> = Vec::new();
+        let mut body = InstrSeq::List(vec![
+            Instruct::IContFlow(InstructControlFlow::Jmp(Label::Regular(5))),
+            Instruct::ILabel(Label::Regular(5)),
+            Instruct::IContFlow(InstructControlFlow::RetC),
+        ]);
+        assert!(try_relabel_function(&alloc, &mut params, &mut body).is_ok());
+        let defs = create_label_to_offset_map(&body);
+        assert_eq!(defs.len(), 1);
+        assert!(defs.contains_key(&0));
+    }
+
+    #[test]
+    fn try_relabel_function_rejects_a_malformed_body_without_panicking() {
+        let alloc = bumpalo::Bump::new();
+        let mut params: Vec<HhasParam> = Vec::new();
+        let mut body = InstrSeq::List(vec![Instruct::IContFlow(InstructControlFlow::Jmp(
+            Label::Regular(42),
+        ))]);
+        assert_eq!(
+            try_relabel_function(&alloc, &mut params, &mut body),
+            Err(LabelError::Undefined(42))
+        );
+    }
+}